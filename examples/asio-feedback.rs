@@ -1,7 +1,9 @@
 //! Feeds back the input stream directly into the output stream.
 //!
-//! Assumes that the input and output devices can use the same stream configuration and that they
-//! support the i32 sample format.
+//! Assumes that the input and output devices can use the same stream configuration.
+//! The device's actual sample format is detected at runtime and I16, U16, I32 and F32 are
+//! handled, so this works unmodified across hosts such as WASAPI (f32) and ASIO (i32/i16).
+//! Any other sample format is rejected with an error.
 //!
 //! Uses a delay of `latency_ms` milliseconds in case the default input and output streams are not
 //! precisely synchronised.
@@ -11,84 +13,227 @@
 //  https://github.com/RustAudio/cpal/blob/310160fbbf507bc27ef751a976550692540b6b9e/examples/feedback.rs
 // This file has been modified by note_kdia to support ASIO devices.
 
+use std::path::PathBuf;
 use std::time::Duration;
 
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{Device, SampleFormat};
 use ringbuf::HeapRb;
 
+/// When set via `--record` or `ASIO_RECORD_WAV`, the captured input is also written to this
+/// WAV file while it's being fed back to the output, turning the pure feedback demo into a
+/// usable monitoring+recording example.
+const RECORD_FLAG: &str = "--record";
+const RECORD_ENV: &str = "ASIO_RECORD_WAV";
+const DEFAULT_RECORD_PATH: &str = "recorded.wav";
+
+/// Name of the input device to select, taken from `--input-device` or the
+/// `ASIO_INPUT_DEVICE` environment variable. Many ASIO drivers expose capture
+/// and playback as distinct devices, so using a single `default_output_device()`
+/// for both streams silently drops the input stream on those drivers.
+const INPUT_DEVICE_FLAG: &str = "--input-device";
+const OUTPUT_DEVICE_FLAG: &str = "--output-device";
+const INPUT_DEVICE_ENV: &str = "ASIO_INPUT_DEVICE";
+const OUTPUT_DEVICE_ENV: &str = "ASIO_OUTPUT_DEVICE";
+
+/// Sample rates we'd rather negotiate than whatever the device happens to default to,
+/// in ascending order. The highest one that falls within the device's supported range wins.
+const STANDARD_RATES: [u32; 4] = [24000, 44100, 48000, 96000];
+
+/// Buffer size (in frames) requested via `--buffer-frames` or `ASIO_BUFFER_FRAMES`, for
+/// trading latency against stability on jittery ASIO drivers. Only honoured when it falls
+/// inside the chosen config's supported buffer size range.
+const BUFFER_FRAMES_FLAG: &str = "--buffer-frames";
+const BUFFER_FRAMES_ENV: &str = "ASIO_BUFFER_FRAMES";
+
+/// Selects between the default ring-buffer feedback loop and the channel-based worker
+/// architecture, via `--architecture worker` or `ASIO_ARCHITECTURE=worker`.
+const ARCHITECTURE_FLAG: &str = "--architecture";
+const ARCHITECTURE_ENV: &str = "ASIO_ARCHITECTURE";
+
+/// Depth of the `mpsc` channels connecting the real-time callbacks to the worker thread in
+/// the channel-based architecture. Kept small since a backed-up queue just means more
+/// latency, not more correctness.
+const WORKER_QUEUE_DEPTH: usize = 5;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Architecture {
+    /// The default: callbacks copy samples directly through a `ringbuf` ring buffer.
+    Ring,
+    /// Callbacks only move `Vec<T>` buffers across bounded channels; a dedicated worker
+    /// thread does the actual processing off the real-time path.
+    Worker,
+}
+
+fn architecture_override() -> Architecture {
+    match name_override(ARCHITECTURE_FLAG, ARCHITECTURE_ENV).as_deref() {
+        Some("worker") => Architecture::Worker,
+        _ => Architecture::Ring,
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
 fn main() -> anyhow::Result<()> {
-    type SampleFormat = i32;
     let channels = 2;
     let latency_ms: f32 = 150.0;
 
     // Select ASIO host
     #[cfg(target_os = "windows")]
     let host = cpal::host_from_id(cpal::HostId::Asio).expect("failed to initialise ASIO host");
+    #[cfg(not(target_os = "windows"))]
+    let host = cpal::default_host();
 
-    // Find device
-    let asio_device = host
-        .default_output_device()
-        .expect("failed to find asio device");
-
-    println!("Using asio device: \"{}\"", asio_device.name()?);
-
-    // Check if ASIO device's sample format is i32
-    // If this assertion fails, change SampleFormat type declared above
-    let mut supported_configs_range = asio_device
-        .supported_input_configs()
-        .expect("error while querying configs");
-    let supported_config = supported_configs_range
-        .next()
-        .unwrap()
-        .with_max_sample_rate();
-    assert_eq!(
-        supported_config.sample_format().to_string(),
-        std::any::type_name::<SampleFormat>()
-    );
+    let input_device_name = name_override(INPUT_DEVICE_FLAG, INPUT_DEVICE_ENV);
+    let output_device_name = name_override(OUTPUT_DEVICE_FLAG, OUTPUT_DEVICE_ENV);
+
+    let input_device = find_input_device(&host, input_device_name.as_deref())?;
+    let output_device = find_output_device(&host, output_device_name.as_deref())?;
+
+    println!("Using input device: \"{}\"", input_device.name()?);
+    println!("Using output device: \"{}\"", output_device.name()?);
 
     // We'll try and use the same configuration between streams to keep it simple.
-    let config_default: cpal::StreamConfig = asio_device.default_input_config()?.into();
-    let config = cpal::StreamConfig {
-        channels,
-        sample_rate: config_default.sample_rate,
-        buffer_size: config_default.buffer_size,
-    };
+    let buffer_frames = buffer_frames_override();
+    let (sample_format, config) =
+        negotiate_config(&input_device, &output_device, channels, buffer_frames)?;
+
+    println!(
+        "Attempting to build both streams with {:?} samples and `{:?}`.",
+        sample_format, config
+    );
 
+    let record_path = record_path_override();
+    let architecture = architecture_override();
+
+    match (architecture, sample_format) {
+        (Architecture::Ring, SampleFormat::I16) => run_feedback::<i16>(
+            &input_device,
+            &output_device,
+            &config,
+            latency_ms,
+            record_path,
+        ),
+        (Architecture::Ring, SampleFormat::U16) => run_feedback::<u16>(
+            &input_device,
+            &output_device,
+            &config,
+            latency_ms,
+            record_path,
+        ),
+        (Architecture::Ring, SampleFormat::I32) => run_feedback::<i32>(
+            &input_device,
+            &output_device,
+            &config,
+            latency_ms,
+            record_path,
+        ),
+        (Architecture::Ring, SampleFormat::F32) => run_feedback::<f32>(
+            &input_device,
+            &output_device,
+            &config,
+            latency_ms,
+            record_path,
+        ),
+        (Architecture::Worker, SampleFormat::I16) => run_feedback_worker::<i16>(
+            &input_device,
+            &output_device,
+            &config,
+            record_path,
+            Box::new(PassThrough),
+        ),
+        (Architecture::Worker, SampleFormat::U16) => run_feedback_worker::<u16>(
+            &input_device,
+            &output_device,
+            &config,
+            record_path,
+            Box::new(PassThrough),
+        ),
+        (Architecture::Worker, SampleFormat::I32) => run_feedback_worker::<i32>(
+            &input_device,
+            &output_device,
+            &config,
+            record_path,
+            Box::new(PassThrough),
+        ),
+        (Architecture::Worker, SampleFormat::F32) => run_feedback_worker::<f32>(
+            &input_device,
+            &output_device,
+            &config,
+            record_path,
+            Box::new(Gain { gain: 0.5 }),
+        ),
+        (_, other) => anyhow::bail!("unsupported sample format: {:?}", other),
+    }
+}
+
+/// Builds the input/output streams for a concrete sample type and runs the feedback loop for
+/// 3 seconds. Generic over `T` so the same code path serves every `cpal::SampleFormat` the
+/// device reports, rather than requiring the user to pick a `SampleFormat` type alias and
+/// recompile. When `record_path` is set, captured frames are also teed to a WAV file.
+fn run_feedback<T>(
+    input_device: &Device,
+    output_device: &Device,
+    config: &cpal::StreamConfig,
+    latency_ms: f32,
+    record_path: Option<PathBuf>,
+) -> anyhow::Result<()>
+where
+    T: cpal::Sample + cpal::SizedSample + RecordableSample + Send + std::fmt::Debug + 'static,
+{
     // Create a delay in case the input and output devices aren't synced.
     let latency_frames = (latency_ms / 1_000.0) * config.sample_rate.0 as f32;
     let latency_samples = latency_frames as usize * config.channels as usize;
 
     // The buffer to share samples
-    let ring = HeapRb::<SampleFormat>::new(latency_samples * 2);
+    let ring = HeapRb::<T>::new(latency_samples * 2);
     let (mut producer, mut consumer) = ring.split();
 
-    // Fill the samples with 0.0 equal to the length of the delay.
+    // Fill the samples with equilibrium equal to the length of the delay.
     for _ in 0..latency_samples {
         // The ring buffer has twice as much space as necessary to add latency here,
         // so this should never fail
-        producer.push(0).unwrap();
+        producer.push(T::EQUILIBRIUM).unwrap();
     }
 
-    let input_data_fn = move |data: &[SampleFormat], _: &cpal::InputCallbackInfo| {
+    // When recording, a second ring buffer tees captured frames to a writer thread so the
+    // real-time input callback never touches the filesystem.
+    let (recorder, mut record_producer) = match &record_path {
+        Some(path) => {
+            let (recorder, producer) = Recorder::<T>::spawn(path.clone(), config)?;
+            (Some(recorder), Some(producer))
+        }
+        None => (None, None),
+    };
+
+    let input_data_fn = move |data: &[T], _: &cpal::InputCallbackInfo| {
         let mut output_fell_behind = false;
+        let mut recorder_fell_behind = false;
         for &sample in data {
             if producer.push(sample).is_err() {
                 output_fell_behind = true;
             }
+            if let Some(record_producer) = record_producer.as_mut() {
+                if record_producer.push(sample).is_err() {
+                    recorder_fell_behind = true;
+                }
+            }
         }
         if output_fell_behind {
             eprintln!("output stream fell behind: try increasing latency",);
         }
+        if recorder_fell_behind {
+            eprintln!("recorder fell behind: dropping samples from the WAV recording");
+        }
     };
 
-    let output_data_fn = move |data: &mut [SampleFormat], _: &cpal::OutputCallbackInfo| {
+    let output_data_fn = move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
         let mut input_fell_behind = false;
         for sample in data {
             *sample = match consumer.pop() {
                 Some(s) => s,
                 None => {
                     input_fell_behind = true;
-                    0
+                    T::EQUILIBRIUM
                 }
             };
         }
@@ -97,14 +242,8 @@ fn main() -> anyhow::Result<()> {
         }
     };
 
-    // Build streams
-    println!(
-        "Attempting to build both streams with i32 samples and `{:?}`.",
-        config
-    );
-
-    let input_stream = asio_device.build_input_stream(&config, input_data_fn, err_fn, None)?;
-    let output_stream = asio_device.build_output_stream(&config, output_data_fn, err_fn, None)?;
+    let input_stream = input_device.build_input_stream(config, input_data_fn, err_fn, None)?;
+    let output_stream = output_device.build_output_stream(config, output_data_fn, err_fn, None)?;
     println!("Successfully built streams.");
 
     // Play the streams.
@@ -121,10 +260,623 @@ fn main() -> anyhow::Result<()> {
 
     drop(input_stream);
     drop(output_stream);
+    if let Some(recorder) = recorder {
+        recorder.finish()?;
+    }
+    println!("Done!");
+    Ok(())
+}
+
+/// A DSP step that can be dropped into the channel-based worker architecture. Runs entirely
+/// on the worker thread, so unlike `run_feedback`'s ring-buffer copy it may allocate or block
+/// without stalling the real-time callbacks; if a buffer is not processed in time, the output
+/// callback plays silence for that buffer.
+trait Processor<T>: Send {
+    fn process(&mut self, frames: &mut [T]);
+}
+
+/// Ships frames through unmodified; the worker architecture's processor for integer sample
+/// formats (F32 streams get a [`Gain`] of 0.5 instead).
+struct PassThrough;
+
+impl<T> Processor<T> for PassThrough {
+    fn process(&mut self, _frames: &mut [T]) {}
+}
+
+/// Scales every frame by a fixed gain factor.
+struct Gain {
+    gain: f32,
+}
+
+impl Processor<f32> for Gain {
+    fn process(&mut self, frames: &mut [f32]) {
+        for frame in frames {
+            *frame *= self.gain;
+        }
+    }
+}
+
+/// Alternative to [`run_feedback`]: instead of copying samples through a ring buffer inside
+/// the real-time callbacks, the callbacks only move `Vec<T>` buffers across bounded `mpsc`
+/// channels. A dedicated worker thread drains the captured buffers, runs `processor` over
+/// them, and hands them back for the output callback to play &mdash; so arbitrarily heavier
+/// processing than a ring-buffer copy can run off the audio thread. Buffers are recycled
+/// through a `free` channel rather than allocated per callback. No `latency_ms` delay is
+/// added here: the output callback plays silence until the first processed buffer arrives.
+fn run_feedback_worker<T>(
+    input_device: &Device,
+    output_device: &Device,
+    config: &cpal::StreamConfig,
+    record_path: Option<PathBuf>,
+    mut processor: Box<dyn Processor<T>>,
+) -> anyhow::Result<()>
+where
+    T: cpal::Sample + cpal::SizedSample + RecordableSample + Send + 'static,
+{
+    let (free_tx, free_rx) = std::sync::mpsc::sync_channel::<Vec<T>>(WORKER_QUEUE_DEPTH);
+    let (captured_tx, captured_rx) = std::sync::mpsc::sync_channel::<Vec<T>>(WORKER_QUEUE_DEPTH);
+    let (processed_tx, processed_rx) = std::sync::mpsc::sync_channel::<Vec<T>>(WORKER_QUEUE_DEPTH);
+
+    // Pre-size the pooled buffers to the callback's expected frame count so `input_data_fn`'s
+    // `extend_from_slice` never has to grow (and thus allocate) on the real-time thread.
+    let buffer_capacity = expected_buffer_frames(config) * config.channels as usize;
+    for _ in 0..WORKER_QUEUE_DEPTH {
+        free_tx
+            .send(Vec::with_capacity(buffer_capacity))
+            .expect("free channel has capacity");
+    }
+
+    let (recorder, mut record_producer) = match &record_path {
+        Some(path) => {
+            let (recorder, producer) = Recorder::<T>::spawn(path.clone(), config)?;
+            (Some(recorder), Some(producer))
+        }
+        None => (None, None),
+    };
+
+    let worker_free_tx = free_tx.clone();
+    let worker = std::thread::spawn(move || {
+        while let Ok(mut buf) = captured_rx.recv() {
+            processor.process(&mut buf);
+            if let Some(record_producer) = record_producer.as_mut() {
+                let mut recorder_fell_behind = false;
+                for &sample in buf.iter() {
+                    if record_producer.push(sample).is_err() {
+                        recorder_fell_behind = true;
+                    }
+                }
+                if recorder_fell_behind {
+                    eprintln!("recorder fell behind: dropping samples from the WAV recording");
+                }
+            }
+            processor.process(&mut buf);
+            // Hand the buffer back to the output callback, or return it straight to the free
+            // pool if that channel is backed up too, so a slow output never shrinks the pool.
+            if let Err(std::sync::mpsc::TrySendError::Full(buf)) = processed_tx.try_send(buf) {
+                let _ = worker_free_tx.try_send(buf);
+            }
+        }
+    });
+
+    let input_free_tx = free_tx.clone();
+    let input_data_fn = move |data: &[T], _: &cpal::InputCallbackInfo| {
+        let Ok(mut buf) = free_rx.try_recv() else {
+            eprintln!("worker fell behind: no free buffer, dropping input");
+            return;
+        };
+        buf.clear();
+        buf.extend_from_slice(data);
+        // If the worker is backed up, return the buffer to the free pool instead of dropping
+        // it, so a transient stall doesn't permanently shrink the pool.
+        if let Err(std::sync::mpsc::TrySendError::Full(buf)) = captured_tx.try_send(buf) {
+            eprintln!("worker fell behind: dropping captured buffer");
+            let _ = input_free_tx.try_send(buf);
+        }
+    };
+
+    let output_data_fn =
+        move |data: &mut [T], _: &cpal::OutputCallbackInfo| match processed_rx.try_recv() {
+            Ok(buf) => {
+                let len = data.len().min(buf.len());
+                data[..len].copy_from_slice(&buf[..len]);
+                for sample in &mut data[len..] {
+                    *sample = T::EQUILIBRIUM;
+                }
+                let _ = free_tx.try_send(buf);
+            }
+            Err(_) => {
+                eprintln!("worker fell behind: playing silence");
+                for sample in data {
+                    *sample = T::EQUILIBRIUM;
+                }
+            }
+        };
+
+    let input_stream = input_device.build_input_stream(config, input_data_fn, err_fn, None)?;
+    let output_stream = output_device.build_output_stream(config, output_data_fn, err_fn, None)?;
+    println!("Successfully built streams (channel-based worker architecture).");
+
+    input_stream.play()?;
+    output_stream.play()?;
+
+    println!("Feedback for 3 secs");
+    std::thread::sleep(Duration::from_secs(3));
+
+    // Dropping the input stream drops its `captured_tx`, which unblocks `worker`'s `recv()`.
+    drop(input_stream);
+    drop(output_stream);
+    worker
+        .join()
+        .map_err(|_| anyhow::anyhow!("worker thread panicked"))?;
+    if let Some(recorder) = recorder {
+        recorder.finish()?;
+    }
     println!("Done!");
     Ok(())
 }
 
+/// Bridges a cpal sample type to the WAV format `hound` should write it as. Implemented for
+/// the sample types `run_feedback` supports; `U16` is re-centred to a signed 16-bit sample
+/// since `hound` has no unsigned format.
+trait RecordableSample: cpal::Sample {
+    fn wav_bits_per_sample() -> u16;
+    fn wav_sample_format() -> hound::SampleFormat;
+    fn write_wav_sample<W: std::io::Write + std::io::Seek>(
+        self,
+        writer: &mut hound::WavWriter<W>,
+    ) -> hound::Result<()>;
+}
+
+impl RecordableSample for i16 {
+    fn wav_bits_per_sample() -> u16 {
+        16
+    }
+    fn wav_sample_format() -> hound::SampleFormat {
+        hound::SampleFormat::Int
+    }
+    fn write_wav_sample<W: std::io::Write + std::io::Seek>(
+        self,
+        writer: &mut hound::WavWriter<W>,
+    ) -> hound::Result<()> {
+        writer.write_sample(self)
+    }
+}
+
+impl RecordableSample for u16 {
+    fn wav_bits_per_sample() -> u16 {
+        16
+    }
+    fn wav_sample_format() -> hound::SampleFormat {
+        hound::SampleFormat::Int
+    }
+    fn write_wav_sample<W: std::io::Write + std::io::Seek>(
+        self,
+        writer: &mut hound::WavWriter<W>,
+    ) -> hound::Result<()> {
+        writer.write_sample(self as i32 - i32::from(u16::MAX / 2) - 1)
+    }
+}
+
+impl RecordableSample for i32 {
+    fn wav_bits_per_sample() -> u16 {
+        32
+    }
+    fn wav_sample_format() -> hound::SampleFormat {
+        hound::SampleFormat::Int
+    }
+    fn write_wav_sample<W: std::io::Write + std::io::Seek>(
+        self,
+        writer: &mut hound::WavWriter<W>,
+    ) -> hound::Result<()> {
+        writer.write_sample(self)
+    }
+}
+
+impl RecordableSample for f32 {
+    fn wav_bits_per_sample() -> u16 {
+        32
+    }
+    fn wav_sample_format() -> hound::SampleFormat {
+        hound::SampleFormat::Float
+    }
+    fn write_wav_sample<W: std::io::Write + std::io::Seek>(
+        self,
+        writer: &mut hound::WavWriter<W>,
+    ) -> hound::Result<()> {
+        writer.write_sample(self)
+    }
+}
+
+/// Tees captured frames to a `hound::WavWriter` running on its own thread. The real-time
+/// input callback only pushes onto a bounded ring buffer; this struct owns the writer thread
+/// and consumer side, and finalizes the WAV file once the capture window ends.
+struct Recorder<T> {
+    // `None` once the writer thread has been joined, by either `finish` or `Drop`.
+    handle: Option<std::thread::JoinHandle<hound::Result<()>>>,
+    stop: std::sync::Arc<std::sync::atomic::AtomicBool>,
+    _sample: std::marker::PhantomData<T>,
+}
+
+impl<T> Recorder<T>
+where
+    T: RecordableSample + Send + 'static,
+{
+    /// Creates the WAV writer and starts the draining thread, returning the `Recorder` handle
+    /// alongside the producer the real-time input callback should push samples onto.
+    fn spawn(
+        path: PathBuf,
+        config: &cpal::StreamConfig,
+    ) -> anyhow::Result<(Self, ringbuf::HeapProducer<T>)> {
+        let spec = hound::WavSpec {
+            channels: config.channels,
+            sample_rate: config.sample_rate.0,
+            bits_per_sample: T::wav_bits_per_sample(),
+            sample_format: T::wav_sample_format(),
+        };
+        let mut writer = hound::WavWriter::create(&path, spec)?;
+
+        // One second of headroom, sized in samples (frames * channels) like the feedback
+        // ring buffer above, so a stereo stream doesn't end up with half as much buffering
+        // as a mono one.
+        let capacity = config.sample_rate.0 as usize * config.channels as usize;
+        let ring = HeapRb::<T>::new(capacity);
+        let (producer, mut consumer) = ring.split();
+
+        let stop = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let handle = std::thread::spawn(move || -> hound::Result<()> {
+            loop {
+                match consumer.pop() {
+                    Some(sample) => sample.write_wav_sample(&mut writer)?,
+                    None => {
+                        if thread_stop.load(std::sync::atomic::Ordering::Relaxed) {
+                            break;
+                        }
+                        std::thread::sleep(Duration::from_millis(10));
+                    }
+                }
+            }
+            writer.finalize()
+        });
+
+        println!("Recording input to \"{}\".", path.display());
+        Ok((
+            Self {
+                handle: Some(handle),
+                stop,
+                _sample: std::marker::PhantomData,
+            },
+            producer,
+        ))
+    }
+
+    /// Signals the writer thread to drain the rest of the buffer and finalize the WAV file,
+    /// surfacing any error encountered doing so. `Drop` does the same thing on any path that
+    /// doesn't reach this call (e.g. an early `?` return), so the writer thread is always
+    /// stopped and the file always finalized, just without an error to report in that case.
+    fn finish(mut self) -> anyhow::Result<()> {
+        self.stop.store(true, std::sync::atomic::Ordering::Relaxed);
+        self.handle
+            .take()
+            .expect("handle is only taken once, here or in Drop")
+            .join()
+            .map_err(|_| anyhow::anyhow!("recorder thread panicked"))??;
+        Ok(())
+    }
+}
+
+impl<T> Drop for Recorder<T> {
+    /// Backstop for callers that never reach `finish()`, e.g. because a `?` elsewhere in
+    /// `run_feedback`/`run_feedback_worker` returns early while a `Recorder` is still in scope.
+    /// Without this, the writer thread leaks (it spins forever polling an abandoned consumer)
+    /// and the WAV file is left unfinalized. Errors are only logged since `drop` can't
+    /// propagate them.
+    fn drop(&mut self) {
+        self.stop.store(true, std::sync::atomic::Ordering::Relaxed);
+        let Some(handle) = self.handle.take() else {
+            return;
+        };
+        match handle.join() {
+            Ok(Ok(())) => {}
+            Ok(Err(err)) => eprintln!("failed to finalize WAV recording: {err}"),
+            Err(_) => eprintln!("recorder thread panicked"),
+        }
+    }
+}
+
+/// Picks a sample rate, sample format and buffer size that both `input_device` and
+/// `output_device` support, preferring the highest entry of `STANDARD_RATES` that fits
+/// within the *intersection* of their supported ranges. Negotiating against `input_device`
+/// alone (as a single-device example can get away with) doesn't hold once input and output
+/// can be independently-selected devices: a config the input device supports may not be
+/// buildable on the output device at all. Falls back to the input device's default config,
+/// but only after confirming the output device also supports it.
+fn negotiate_config(
+    input_device: &Device,
+    output_device: &Device,
+    channels: u16,
+    buffer_frames: Option<u32>,
+) -> anyhow::Result<(SampleFormat, cpal::StreamConfig)> {
+    let input_configs: Vec<_> = input_device.supported_input_configs()?.collect();
+    let output_configs: Vec<_> = output_device.supported_output_configs()?.collect();
+
+    let mut best: Option<(u32, SampleFormat, cpal::SupportedBufferSize)> = None;
+    for in_range in &input_configs {
+        for out_range in &output_configs {
+            if in_range.sample_format() != out_range.sample_format() {
+                continue;
+            }
+            let min_rate = in_range
+                .min_sample_rate()
+                .0
+                .max(out_range.min_sample_rate().0);
+            let max_rate = in_range
+                .max_sample_rate()
+                .0
+                .min(out_range.max_sample_rate().0);
+            if min_rate > max_rate {
+                continue;
+            }
+            for &rate in STANDARD_RATES.iter().rev() {
+                if rate < min_rate || rate > max_rate {
+                    continue;
+                }
+                let is_better = match &best {
+                    Some((best_rate, _, _)) => rate > *best_rate,
+                    None => true,
+                };
+                if is_better {
+                    let buffer_size =
+                        intersect_buffer_size(in_range.buffer_size(), out_range.buffer_size());
+                    best = Some((rate, in_range.sample_format(), buffer_size));
+                }
+                break;
+            }
+        }
+    }
+
+    let (sample_format, sample_rate, buffer_size_range) = match best {
+        Some((rate, format, buffer_size)) => (format, cpal::SampleRate(rate), buffer_size),
+        None => {
+            println!(
+                "No standard sample rate supported by both devices, falling back to the input device default."
+            );
+            let default = input_device.default_input_config()?;
+            let format = default.sample_format();
+            let rate = default.sample_rate();
+            let output_supports = output_configs.iter().any(|out_range| {
+                out_range.sample_format() == format
+                    && rate.0 >= out_range.min_sample_rate().0
+                    && rate.0 <= out_range.max_sample_rate().0
+            });
+            if !output_supports {
+                anyhow::bail!(
+                    "output device does not support the input device's default config ({:?}, {} Hz)",
+                    format,
+                    rate.0
+                );
+            }
+            (format, rate, cpal::SupportedBufferSize::Unknown)
+        }
+    };
+
+    let buffer_size = match (buffer_frames, &buffer_size_range) {
+        (Some(frames), cpal::SupportedBufferSize::Range { min, max })
+            if frames >= *min && frames <= *max =>
+        {
+            cpal::BufferSize::Fixed(frames)
+        }
+        _ => cpal::BufferSize::Default,
+    };
+
+    Ok((
+        sample_format,
+        cpal::StreamConfig {
+            channels,
+            sample_rate,
+            buffer_size,
+        },
+    ))
+}
+
+/// Narrows two devices' supported buffer size ranges down to the range both can build,
+/// falling back to `Unknown` when either side doesn't expose a range or the ranges don't
+/// overlap.
+fn intersect_buffer_size(
+    a: &cpal::SupportedBufferSize,
+    b: &cpal::SupportedBufferSize,
+) -> cpal::SupportedBufferSize {
+    match (a, b) {
+        (
+            cpal::SupportedBufferSize::Range {
+                min: min_a,
+                max: max_a,
+            },
+            cpal::SupportedBufferSize::Range {
+                min: min_b,
+                max: max_b,
+            },
+        ) => {
+            let min = *min_a.max(min_b);
+            let max = *max_a.min(max_b);
+            if min <= max {
+                cpal::SupportedBufferSize::Range { min, max }
+            } else {
+                cpal::SupportedBufferSize::Unknown
+            }
+        }
+        _ => cpal::SupportedBufferSize::Unknown,
+    }
+}
+
+/// Frame count to pre-size the worker architecture's pooled buffers with when the negotiated
+/// config didn't pin down a fixed buffer size. Picked to comfortably cover typical host-chosen
+/// buffer sizes; an oversized callback still works, it just forces one reallocation.
+const DEFAULT_WORKER_BUFFER_FRAMES: usize = 4096;
+
+/// Best estimate of how many frames a single audio callback will hand the worker architecture,
+/// used to pre-size its pooled buffers so the real-time callbacks never allocate.
+fn expected_buffer_frames(config: &cpal::StreamConfig) -> usize {
+    match config.buffer_size {
+        cpal::BufferSize::Fixed(frames) => frames as usize,
+        cpal::BufferSize::Default => DEFAULT_WORKER_BUFFER_FRAMES,
+    }
+}
+
+/// Reads `--buffer-frames`/`ASIO_BUFFER_FRAMES` as a frame count, if set.
+fn buffer_frames_override() -> Option<u32> {
+    name_override(BUFFER_FRAMES_FLAG, BUFFER_FRAMES_ENV).and_then(|s| s.parse().ok())
+}
+
+/// Reads `--record`/`ASIO_RECORD_WAV` as a WAV output path, defaulting to
+/// `recorded.wav` when the flag/env var is set without a value.
+fn record_path_override() -> Option<PathBuf> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == RECORD_FLAG {
+            return Some(
+                args.next()
+                    .map(PathBuf::from)
+                    .unwrap_or_else(|| DEFAULT_RECORD_PATH.into()),
+            );
+        }
+    }
+    std::env::var(RECORD_ENV).ok().map(|v| {
+        if v.is_empty() {
+            DEFAULT_RECORD_PATH.into()
+        } else {
+            PathBuf::from(v)
+        }
+    })
+}
+
+/// Reads an override value (a device name, architecture or buffer size) from the matching
+/// `flag` (e.g. `--input-device foo`) or, failing that, the `env_var`. Returns `None` when
+/// neither is set, in which case callers fall back to their default.
+fn name_override(flag: &str, env_var: &str) -> Option<String> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == flag {
+            return args.next();
+        }
+    }
+    std::env::var(env_var).ok()
+}
+
+/// Picks the input device to capture from: a name override, the first device from
+/// `host.input_devices()`, or `default_input_device()` if enumeration comes back empty.
+fn find_input_device(host: &cpal::Host, name: Option<&str>) -> anyhow::Result<Device> {
+    if let Some(name) = name {
+        for device in host.input_devices()? {
+            if device.name()? == name {
+                return Ok(device);
+            }
+        }
+        anyhow::bail!("no input device named \"{}\"", name);
+    }
+
+    if let Some(device) = host.input_devices()?.next() {
+        return Ok(device);
+    }
+
+    host.default_input_device()
+        .ok_or_else(|| anyhow::anyhow!("no input device available"))
+}
+
+/// Picks the output device to play back to: a name override, the first device from
+/// `host.output_devices()`, or `default_output_device()` if enumeration comes back empty.
+fn find_output_device(host: &cpal::Host, name: Option<&str>) -> anyhow::Result<Device> {
+    if let Some(name) = name {
+        for device in host.output_devices()? {
+            if device.name()? == name {
+                return Ok(device);
+            }
+        }
+        anyhow::bail!("no output device named \"{}\"", name);
+    }
+
+    if let Some(device) = host.output_devices()?.next() {
+        return Ok(device);
+    }
+
+    host.default_output_device()
+        .ok_or_else(|| anyhow::anyhow!("no output device available"))
+}
+
 fn err_fn(err: cpal::StreamError) {
     eprintln!("an error occurred on stream: {}", err);
 }
+
+/// `examples/*.rs` compiles as a binary target, so a `wasm32-unknown-unknown` build still
+/// needs a `fn main`, even though the wasm-bindgen-exported functions in `mod wasm` are the
+/// real entry points. It does nothing; the `#[wasm_bindgen(start)]` function runs on load and
+/// `start_feedback`/`stop_feedback` are called from JS.
+#[cfg(target_arch = "wasm32")]
+fn main() {}
+
+/// Web entry point: cpal's WebAudio host only exposes an output device, and browsers require
+/// playback to begin from a user gesture, so this path generates its own tone instead of
+/// feeding back a captured input and waits to be kicked off by [`start_feedback`] rather than
+/// sleeping on a timer like the native `main` does. Load `asio-feedback-wasm.html` (see the
+/// `examples/` directory) to drive it.
+#[cfg(target_arch = "wasm32")]
+mod wasm {
+    use std::cell::RefCell;
+    use std::f32::consts::PI;
+
+    use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+    use wasm_bindgen::prelude::*;
+
+    /// Keeps the output stream alive for as long as the page is open; dropping it would stop
+    /// playback, and there's no natural Rust owner for it once `start_feedback` returns to JS.
+    thread_local! {
+        static STREAM: RefCell<Option<cpal::Stream>> = RefCell::new(None);
+    }
+
+    #[wasm_bindgen(start)]
+    pub fn main_js() {
+        console_error_panic_hook::set_once();
+    }
+
+    /// Builds and plays the output stream. Exported to JS so it can only run after a user
+    /// gesture (e.g. a button click), as required by browser autoplay policies.
+    #[wasm_bindgen]
+    pub fn start_feedback() -> Result<(), JsValue> {
+        let to_js_err = |err: impl std::fmt::Display| JsValue::from_str(&err.to_string());
+
+        let host = cpal::default_host();
+        let device = host
+            .default_output_device()
+            .ok_or_else(|| to_js_err("no output device available"))?;
+        let config = device.default_output_config().map_err(to_js_err)?;
+
+        let sample_rate = config.sample_rate().0 as f32;
+        let channels = config.channels() as usize;
+        let mut phase = 0.0f32;
+        let frequency = 440.0f32;
+
+        let output_data_fn = move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
+            for frame in data.chunks_mut(channels) {
+                let sample = (phase * 2.0 * PI).sin() * 0.2;
+                for out in frame {
+                    *out = sample;
+                }
+                phase = (phase + frequency / sample_rate).fract();
+            }
+        };
+
+        let stream = device
+            .build_output_stream(&config.into(), output_data_fn, super::err_fn, None)
+            .map_err(to_js_err)?;
+        stream.play().map_err(to_js_err)?;
+
+        STREAM.with(|cell| *cell.borrow_mut() = Some(stream));
+        Ok(())
+    }
+
+    /// Stops playback; exported so the HTML harness can offer a matching stop button.
+    #[wasm_bindgen]
+    pub fn stop_feedback() {
+        STREAM.with(|cell| *cell.borrow_mut() = None);
+    }
+}